@@ -0,0 +1,350 @@
+//! Self-contained archive format bundling a KCD, its HDR, and all videos it
+//! references into one file, indexed by a catalog so individual members can
+//! be extracted without reading the whole archive.
+//!
+//! Layout: `[header][payload 0][payload 1]...[catalog][footer]`. The
+//! catalog lists every member as `(name, offset, length, kind)` laid out as
+//! a flat, array-indexed balanced BST (an Eytzinger layout): for `n` sorted
+//! entries, `catalog[0]` is the subtree root and `catalog[2i+1]` /
+//! `catalog[2i+2]` are the left/right children of `catalog[i]`, so a normal
+//! binary search over the sorted member names maps directly onto array
+//! indices with no pointers to follow. A fixed-size footer at the end of
+//! the file holds the catalog's offset and entry count.
+
+use anyhow::{bail, Context, Result};
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8; 4] = b"KCDP";
+const FORMAT_VERSION: u32 = 1;
+const NAME_LEN: usize = 256;
+/// `offset(8) + length(8) + kind(1)` following the fixed-width name.
+const ENTRY_TAIL_LEN: usize = 17;
+const ENTRY_LEN: usize = NAME_LEN + ENTRY_TAIL_LEN;
+/// `magic(4) + catalog_offset(8) + entry_count(4)`.
+const FOOTER_LEN: u64 = 16;
+
+/// What kind of dataset member a [`CatalogEntry`] refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberKind {
+    Kcd,
+    Hdr,
+    Video,
+}
+
+impl MemberKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Kcd => 0,
+            Self::Hdr => 1,
+            Self::Video => 2,
+        }
+    }
+
+    fn from_byte(b: u8) -> Result<Self> {
+        match b {
+            0 => Ok(Self::Kcd),
+            1 => Ok(Self::Hdr),
+            2 => Ok(Self::Video),
+            other => bail!("Unknown member kind byte: {other}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CatalogEntry {
+    name: String,
+    offset: u64,
+    length: u64,
+    kind: MemberKind,
+}
+
+impl CatalogEntry {
+    fn write(&self, out: &mut impl Write) -> Result<()> {
+        let name_bytes = self.name.as_bytes();
+        if name_bytes.len() > NAME_LEN {
+            bail!("member name is too long (> {NAME_LEN} bytes): {}", self.name);
+        }
+        out.write_all(name_bytes)?;
+        out.write_all(&vec![0u8; NAME_LEN - name_bytes.len()])?;
+        out.write_all(&self.offset.to_be_bytes())?;
+        out.write_all(&self.length.to_be_bytes())?;
+        out.write_all(&[self.kind.to_byte()])?;
+        Ok(())
+    }
+
+    fn read(buf: &[u8]) -> Result<Self> {
+        let name = buf[..NAME_LEN]
+            .iter()
+            .cloned()
+            .filter_map(|v| char::try_from(v).ok())
+            .collect::<String>()
+            .trim_matches(char::from(0))
+            .to_string();
+        let offset = u64::from_be_bytes(buf[NAME_LEN..NAME_LEN + 8].try_into().unwrap());
+        let length = u64::from_be_bytes(buf[NAME_LEN + 8..NAME_LEN + 16].try_into().unwrap());
+        let kind = MemberKind::from_byte(buf[NAME_LEN + 16])?;
+        Ok(Self {
+            name,
+            offset,
+            length,
+            kind,
+        })
+    }
+}
+
+/// One member to be written into an archive: the on-disk name it should be
+/// stored (and later extracted) under, the file supplying its bytes, and
+/// its [`MemberKind`].
+pub struct Member {
+    pub name: String,
+    pub path: PathBuf,
+    pub kind: MemberKind,
+}
+
+/// `order[k]` is the index into a sorted slice that belongs at array
+/// position `k` of the Eytzinger (flat BST) layout.
+fn eytzinger_order(n: usize) -> Vec<usize> {
+    let mut order = vec![0usize; n];
+    let mut next = 0usize;
+    fn visit(order: &mut [usize], pos: usize, n: usize, next: &mut usize) {
+        if pos >= n {
+            return;
+        }
+        visit(order, 2 * pos + 1, n, next);
+        order[pos] = *next;
+        *next += 1;
+        visit(order, 2 * pos + 2, n, next);
+    }
+    visit(&mut order, 0, n, &mut next);
+    order
+}
+
+/// Bundle `members` into `archive_path`: header, concatenated payloads,
+/// then a catalog laid out as a flat BST, then a fixed-size footer.
+pub fn pack(archive_path: impl AsRef<Path>, members: &[Member]) -> Result<()> {
+    let archive_path = archive_path.as_ref();
+    let mut out = BufWriter::new(
+        File::create(archive_path)
+            .with_context(|| format!("Fail to create archive: {}", archive_path.display()))?,
+    );
+    out.write_all(MAGIC)?;
+    out.write_all(&FORMAT_VERSION.to_be_bytes())?;
+
+    let mut sorted: Vec<&Member> = members.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut entries = Vec::with_capacity(sorted.len());
+    let mut offset: u64 = 4 + 4; // magic + format version already written
+    for member in &sorted {
+        let mut reader = BufReader::new(
+            File::open(&member.path)
+                .with_context(|| format!("Fail to open member: {}", member.path.display()))?,
+        );
+        let length = std::io::copy(&mut reader, &mut out)?;
+        entries.push(CatalogEntry {
+            name: member.name.clone(),
+            offset,
+            length,
+            kind: member.kind,
+        });
+        offset += length;
+    }
+
+    let catalog_offset = offset;
+    for idx in eytzinger_order(entries.len()) {
+        entries[idx].write(&mut out)?;
+    }
+
+    out.write_all(MAGIC)?;
+    out.write_all(&catalog_offset.to_be_bytes())?;
+    out.write_all(&(entries.len() as u32).to_be_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+struct Footer {
+    catalog_offset: u64,
+    entry_count: u32,
+}
+
+fn read_footer(file: &mut File) -> Result<Footer> {
+    let len = file.metadata()?.len();
+    if len < FOOTER_LEN {
+        bail!("archive is too small to contain a footer");
+    }
+    file.seek(SeekFrom::Start(len - FOOTER_LEN))?;
+    let mut buf = [0u8; FOOTER_LEN as usize];
+    file.read_exact(&mut buf)?;
+    if &buf[0..4] != MAGIC {
+        bail!("not a valid kcd archive (bad footer magic)");
+    }
+    Ok(Footer {
+        catalog_offset: u64::from_be_bytes(buf[4..12].try_into().unwrap()),
+        entry_count: u32::from_be_bytes(buf[12..16].try_into().unwrap()),
+    })
+}
+
+fn read_catalog(file: &mut File, footer: &Footer) -> Result<Vec<CatalogEntry>> {
+    file.seek(SeekFrom::Start(footer.catalog_offset))?;
+    let mut buf = vec![0u8; ENTRY_LEN * footer.entry_count as usize];
+    file.read_exact(&mut buf)?;
+    buf.chunks(ENTRY_LEN).map(CatalogEntry::read).collect()
+}
+
+/// Binary search the flat BST `catalog` for `name`.
+fn find_entry<'a>(catalog: &'a [CatalogEntry], name: &str) -> Option<&'a CatalogEntry> {
+    let mut i = 0usize;
+    while i < catalog.len() {
+        match name.cmp(&catalog[i].name) {
+            Ordering::Equal => return Some(&catalog[i]),
+            Ordering::Less => i = 2 * i + 1,
+            Ordering::Greater => i = 2 * i + 2,
+        }
+    }
+    None
+}
+
+/// List every member name stored in `archive_path` without reading any
+/// payload.
+pub fn list(archive_path: impl AsRef<Path>) -> Result<Vec<String>> {
+    let archive_path = archive_path.as_ref();
+    let mut file = File::open(archive_path)
+        .with_context(|| format!("Fail to open archive: {}", archive_path.display()))?;
+    let footer = read_footer(&mut file)?;
+    Ok(read_catalog(&mut file, &footer)?
+        .into_iter()
+        .map(|e| e.name)
+        .collect())
+}
+
+/// Seek straight to `member` via the footer and flat-BST catalog, then
+/// stream just its bytes to `dest`.
+pub fn unpack_member(
+    archive_path: impl AsRef<Path>,
+    member: &str,
+    dest: impl AsRef<Path>,
+) -> Result<()> {
+    let archive_path = archive_path.as_ref();
+    let mut file = File::open(archive_path)
+        .with_context(|| format!("Fail to open archive: {}", archive_path.display()))?;
+    let footer = read_footer(&mut file)?;
+    let catalog = read_catalog(&mut file, &footer)?;
+    let entry = find_entry(&catalog, member)
+        .with_context(|| format!("No such member in archive: {member}"))?;
+
+    file.seek(SeekFrom::Start(entry.offset))?;
+    let mut limited = (&mut file).take(entry.length);
+    let mut out = BufWriter::new(
+        File::create(dest.as_ref())
+            .with_context(|| format!("Fail to create: {}", dest.as_ref().display()))?,
+    );
+    std::io::copy(&mut limited, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Extract every member of `archive_path` into `dest_dir`, under its stored
+/// name.
+pub fn unpack_all(archive_path: impl AsRef<Path>, dest_dir: impl AsRef<Path>) -> Result<()> {
+    let archive_path = archive_path.as_ref();
+    let dest_dir = dest_dir.as_ref();
+    std::fs::create_dir_all(dest_dir)?;
+    for name in list(archive_path)? {
+        unpack_member(archive_path, &name, dest_dir.join(&name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn write_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = PathBuf::from(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn read_file(path: impl AsRef<Path>) -> Vec<u8> {
+        let mut out = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn pack_then_unpack_all_round_trips_every_member() -> Result<()> {
+        let kcd = write_file("./archive_test_round_trip.kcd", b"kcd-bytes");
+        let hdr = write_file("./archive_test_round_trip.hdr", b"hdr-bytes-a-bit-longer");
+        let video = write_file(
+            "./archive_test_round_trip.mp4",
+            b"video-bytes-longest-of-the-three",
+        );
+
+        let members = [
+            Member {
+                name: "dataset.kcd".to_string(),
+                path: kcd,
+                kind: MemberKind::Kcd,
+            },
+            Member {
+                name: "dataset.hdr".to_string(),
+                path: hdr,
+                kind: MemberKind::Hdr,
+            },
+            Member {
+                name: "dataset.mp4".to_string(),
+                path: video,
+                kind: MemberKind::Video,
+            },
+        ];
+
+        let archive_path = PathBuf::from("./archive_test_round_trip.kcdp");
+        pack(&archive_path, &members)?;
+
+        let dest_dir = PathBuf::from("./archive_test_round_trip_out");
+        unpack_all(&archive_path, &dest_dir)?;
+
+        for member in &members {
+            let expected = read_file(&member.path);
+            let actual = read_file(dest_dir.join(&member.name));
+            assert_eq!(actual, expected, "member `{}` did not round-trip", member.name);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unpack_member_binary_searches_the_flat_bst_for_any_entry() -> Result<()> {
+        // Enough members that find_entry must actually branch left/right
+        // through the flat BST rather than getting lucky at the root.
+        let names = ["alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf"];
+        let members: Vec<Member> = names
+            .iter()
+            .map(|name| Member {
+                name: format!("{name}.mp4"),
+                path: write_file(
+                    &format!("./archive_test_bst_{name}.mp4"),
+                    format!("payload-{name}").as_bytes(),
+                ),
+                kind: MemberKind::Video,
+            })
+            .collect();
+
+        let archive_path = PathBuf::from("./archive_test_bst.kcdp");
+        pack(&archive_path, &members)?;
+
+        assert_eq!(list(&archive_path)?.len(), names.len());
+
+        for name in names {
+            let dest = PathBuf::from(format!("./archive_test_bst_{name}.out"));
+            unpack_member(&archive_path, &format!("{name}.mp4"), &dest)?;
+            assert_eq!(read_file(&dest), format!("payload-{name}").as_bytes());
+        }
+        Ok(())
+    }
+}
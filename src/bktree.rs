@@ -0,0 +1,172 @@
+//! A small [BK-tree](https://en.wikipedia.org/wiki/BK-tree) for indexing
+//! items under a discrete metric (here, Hamming distance between perceptual
+//! hashes) so that "everything within tolerance `t` of X" queries don't
+//! require an all-pairs scan.
+
+use std::collections::HashMap;
+
+/// A discrete metric a [`BkTree`] can index items by.
+pub trait Hamming {
+    /// Distance to `other`. Must satisfy the triangle inequality for the
+    /// tree's pruning to be correct.
+    fn hamming_distance(&self, other: &Self) -> u32;
+}
+
+struct Node<T> {
+    item: T,
+    // Children keyed by their exact distance to this node.
+    children: HashMap<u32, Box<Node<T>>>,
+}
+
+/// BK-tree over items implementing [`Hamming`].
+#[derive(Default)]
+pub struct BkTree<T> {
+    root: Option<Box<Node<T>>>,
+}
+
+impl<T: Hamming> BkTree<T> {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    pub fn insert(&mut self, item: T) {
+        let Some(root) = &mut self.root else {
+            self.root = Some(Box::new(Node {
+                item,
+                children: HashMap::new(),
+            }));
+            return;
+        };
+        Self::insert_node(root, item);
+    }
+
+    fn insert_node(node: &mut Node<T>, item: T) {
+        let d = node.item.hamming_distance(&item);
+        match node.children.get_mut(&d) {
+            Some(child) => Self::insert_node(child, item),
+            None => {
+                node.children.insert(
+                    d,
+                    Box::new(Node {
+                        item,
+                        children: HashMap::new(),
+                    }),
+                );
+            }
+        }
+    }
+
+    /// Every indexed item within Hamming distance `tolerance` of `target`,
+    /// found by visiting only children whose key lies in `[d-t, d+t]`.
+    pub fn find_within(&self, target: &T, tolerance: u32) -> Vec<&T> {
+        let mut out = Vec::new();
+        if let Some(root) = &self.root {
+            Self::search_node(root, target, tolerance, &mut out);
+        }
+        out
+    }
+
+    fn search_node<'a>(node: &'a Node<T>, target: &T, tolerance: u32, out: &mut Vec<&'a T>) {
+        let d = node.item.hamming_distance(target);
+        if d <= tolerance {
+            out.push(&node.item);
+        }
+        let lo = d.saturating_sub(tolerance);
+        let hi = d + tolerance;
+        for (_, child) in node.children.iter().filter(|(&k, _)| k >= lo && k <= hi) {
+            Self::search_node(child, target, tolerance, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Bits(u32);
+
+    impl Hamming for Bits {
+        fn hamming_distance(&self, other: &Self) -> u32 {
+            (self.0 ^ other.0).count_ones()
+        }
+    }
+
+    #[test]
+    fn duplicate_items_chain_under_same_distance_key() {
+        let mut tree = BkTree::new();
+        tree.insert(Bits(0b0000));
+        tree.insert(Bits(0b0000)); // distance 0 from root -> chains as its distance-0 child
+        tree.insert(Bits(0b0000)); // distance 0 from that child -> chains one level deeper
+
+        let hits = tree.find_within(&Bits(0b0000), 0);
+        assert_eq!(hits.len(), 3);
+    }
+
+    #[test]
+    fn find_within_matches_only_items_inside_the_tolerance_window() {
+        let mut tree = BkTree::new();
+        tree.insert(Bits(0b0000_0000)); // root
+        tree.insert(Bits(0b0000_1111)); // distance 4 from root
+        tree.insert(Bits(0b1111_1111)); // distance 8 from root
+        tree.insert(Bits(0b0000_0001)); // distance 1 from root
+
+        let mut hits: Vec<u32> = tree
+            .find_within(&Bits(0b0000_0000), 1)
+            .into_iter()
+            .map(|b| b.0)
+            .collect();
+        hits.sort_unstable();
+        assert_eq!(hits, vec![0b0000_0000, 0b0000_0001]);
+    }
+
+    /// A [`Hamming`] item that records whether it was ever compared to
+    /// another item, so a test can assert a branch was pruned rather than
+    /// merely that the (correct, pruning-independent) result looks right.
+    struct Tracked {
+        value: u32,
+        visited: Option<Rc<Cell<bool>>>,
+    }
+
+    impl Hamming for Tracked {
+        fn hamming_distance(&self, other: &Self) -> u32 {
+            if let Some(flag) = &self.visited {
+                flag.set(true);
+            }
+            (self.value ^ other.value).count_ones()
+        }
+    }
+
+    #[test]
+    fn find_within_prunes_out_of_window_branches() {
+        let far_visited = Rc::new(Cell::new(false));
+
+        let mut tree = BkTree::new();
+        tree.insert(Tracked {
+            value: 0b0000_0000,
+            visited: None,
+        }); // root
+        tree.insert(Tracked {
+            value: 0b0000_1111,
+            visited: None,
+        }); // distance 4 from root -> becomes root's key-4 child
+        tree.insert(Tracked {
+            value: 0b1111_0000,
+            visited: Some(far_visited.clone()),
+        }); // also distance 4 from root -> chains deeper, under the key-4 child
+
+        let target = Tracked {
+            value: 0b0000_0000,
+            visited: None,
+        };
+        let hits = tree.find_within(&target, 1);
+
+        assert!(
+            !far_visited.get(),
+            "a branch outside [d-t, d+t] must be pruned, not visited"
+        );
+        assert_eq!(hits.len(), 1, "only the root is within tolerance 1");
+    }
+}
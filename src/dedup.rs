@@ -0,0 +1,203 @@
+//! Perceptual near-duplicate detection across one or more HDR files.
+//!
+//! Each referenced video is reduced to a fixed-length perceptual hash
+//! (evenly-spaced sampled frames, downscaled to a small grayscale
+//! thumbnail, thresholded against the frame's own mean brightness), and all
+//! hashes are indexed in a [`BkTree`] so that near-duplicate clusters can be
+//! reported without an all-pairs comparison.
+
+use crate::bktree::{BkTree, Hamming};
+use crate::mp4;
+use crate::path_resolver::PathResolver;
+use anyhow::{Context, Result};
+use ffmpeg_next as ffmpeg;
+use std::path::{Path, PathBuf};
+
+/// Number of evenly-spaced frames sampled per video.
+const SAMPLE_FRAMES: usize = 10;
+/// Side length (pixels) of the grayscale thumbnail each frame is reduced to.
+const THUMB_SIZE: u32 = 32;
+
+/// A perceptual hash: one bit per thumbnail pixel across all sampled
+/// frames, packed into 64-bit words so Hamming distance is a XOR + popcount.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PHash(Vec<u64>);
+
+impl Hamming for PHash {
+    fn hamming_distance(&self, other: &Self) -> u32 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+}
+
+/// Decode [`SAMPLE_FRAMES`] evenly-spaced frames from `path`, downscale each
+/// to a grayscale [`THUMB_SIZE`]x[`THUMB_SIZE`] thumbnail, and pack the
+/// "brighter than this frame's mean" bit of every pixel into one [`PHash`].
+pub fn hash_video(path: impl AsRef<Path>) -> Result<PHash> {
+    let path = path.as_ref();
+    let info = mp4::parse(path).with_context(|| format!("Fail to inspect {}", path.display()))?;
+    let duration = info.duration_secs().max(0.1);
+
+    ffmpeg::init().context("Fail to initialize video decoder")?;
+    let mut ictx = ffmpeg::format::input(&path)
+        .with_context(|| format!("Fail to open video: {}", path.display()))?;
+    let video_stream = ictx
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .context("No video stream found")?;
+    let stream_index = video_stream.index();
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(video_stream.parameters())?
+        .decoder()
+        .video()?;
+
+    let n_words = (SAMPLE_FRAMES * (THUMB_SIZE * THUMB_SIZE) as usize).div_ceil(64);
+    let mut bits = vec![0u64; n_words];
+    let mut bit_offset = 0usize;
+
+    for i in 0..SAMPLE_FRAMES {
+        let timestamp = duration * (i as f64 + 0.5) / SAMPLE_FRAMES as f64;
+        let thumbnail = decode_frame_near(&mut ictx, &mut decoder, stream_index, timestamp)?;
+        for brighter_than_mean in threshold_against_mean(&thumbnail) {
+            if brighter_than_mean {
+                bits[bit_offset / 64] |= 1u64 << (bit_offset % 64);
+            }
+            bit_offset += 1;
+        }
+    }
+
+    Ok(PHash(bits))
+}
+
+/// Seek to `timestamp` seconds, decode the next video frame, and scale it
+/// down to a `THUMB_SIZE`x`THUMB_SIZE` grayscale thumbnail.
+fn decode_frame_near(
+    ictx: &mut ffmpeg::format::context::Input,
+    decoder: &mut ffmpeg::decoder::Video,
+    stream_index: usize,
+    timestamp: f64,
+) -> Result<Vec<u8>> {
+    let position = (timestamp * ffmpeg::ffi::AV_TIME_BASE as f64) as i64;
+    ictx.seek(position, ..position)
+        .with_context(|| format!("Fail to seek to {timestamp:.2}s"))?;
+
+    let mut scaler = ffmpeg::software::scaling::Context::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        ffmpeg::format::Pixel::GRAY8,
+        THUMB_SIZE,
+        THUMB_SIZE,
+        ffmpeg::software::scaling::Flags::BILINEAR,
+    )?;
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        decoder.send_packet(&packet)?;
+        let mut decoded = ffmpeg::frame::Video::empty();
+        if decoder.receive_frame(&mut decoded).is_ok() {
+            let mut thumbnail = ffmpeg::frame::Video::empty();
+            scaler.run(&decoded, &mut thumbnail)?;
+            return Ok(thumbnail.data(0)[..(THUMB_SIZE * THUMB_SIZE) as usize].to_vec());
+        }
+    }
+
+    anyhow::bail!("No frame decoded near {timestamp:.2}s")
+}
+
+fn threshold_against_mean(thumbnail: &[u8]) -> impl Iterator<Item = bool> + '_ {
+    let mean = thumbnail.iter().map(|&p| p as u32).sum::<u32>() / thumbnail.len().max(1) as u32;
+    thumbnail.iter().map(move |&p| p as u32 > mean)
+}
+
+/// A cluster of near-duplicate videos, named by the HDR that referenced
+/// them and the stored filepath within that HDR.
+#[derive(Debug)]
+pub struct Cluster {
+    pub members: Vec<(PathBuf, String)>,
+}
+
+/// Hash every video referenced by `hdrs`, index the hashes in a
+/// [`BkTree`], and report every cluster of near-duplicates: videos linked
+/// by a chain of pairs each within `tolerance` Hamming distance. Two
+/// members of one cluster can be further apart than `tolerance`.
+pub fn find_duplicates<R: PathResolver>(
+    hdrs: &[PathBuf],
+    tolerance: u32,
+    resolver: &R,
+) -> Result<Vec<Cluster>> {
+    struct Entry {
+        hdr: PathBuf,
+        name: String,
+        hash: PHash,
+    }
+    impl Hamming for Entry {
+        fn hamming_distance(&self, other: &Self) -> u32 {
+            self.hash.hamming_distance(&other.hash)
+        }
+    }
+
+    let mut entries = Vec::new();
+    for hdr in hdrs {
+        for (name, video_path) in crate::video_paths(hdr, resolver)? {
+            let hash = hash_video(&video_path)
+                .with_context(|| format!("Fail to hash video: {}", video_path.display()))?;
+            entries.push(Entry {
+                hdr: hdr.clone(),
+                name,
+                hash,
+            });
+        }
+    }
+
+    // BkTree needs owned Hamming items; wrap the index alongside a borrow so
+    // cluster membership can be recovered after the search.
+    struct Indexed<'a>(usize, &'a Entry);
+    impl Hamming for Indexed<'_> {
+        fn hamming_distance(&self, other: &Self) -> u32 {
+            self.1.hash.hamming_distance(&other.1.hash)
+        }
+    }
+    let mut tree: BkTree<Indexed> = BkTree::new();
+    for (i, entry) in entries.iter().enumerate() {
+        tree.insert(Indexed(i, entry));
+    }
+
+    // Union-find over entry indices: anything within tolerance is merged
+    // into the same cluster.
+    let mut parent: Vec<usize> = (0..entries.len()).collect();
+    fn find(parent: &mut [usize], x: usize) -> usize {
+        if parent[x] != x {
+            parent[x] = find(parent, parent[x]);
+        }
+        parent[x]
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        for hit in tree.find_within(&Indexed(i, entry), tolerance) {
+            let (a, b) = (find(&mut parent, i), find(&mut parent, hit.0));
+            if a != b {
+                parent[a] = b;
+            }
+        }
+    }
+
+    let mut clusters: std::collections::HashMap<usize, Vec<(PathBuf, String)>> =
+        std::collections::HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let root = find(&mut parent, i);
+        clusters
+            .entry(root)
+            .or_default()
+            .push((entry.hdr.clone(), entry.name.clone()));
+    }
+
+    Ok(clusters
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| Cluster { members })
+        .collect())
+}
@@ -1,12 +1,23 @@
 use anyhow::{bail, Context, Result};
 use clap::ValueEnum;
 use deku::{bitvec::Msb0, prelude::*};
-use indicatif::{ProgressIterator as _, ProgressStyle};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
 use std::{
     fs::File,
     io::{BufRead, BufReader, BufWriter, Read, Write},
     path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
 };
+
+pub mod archive;
+pub mod bktree;
+pub mod dedup;
+pub mod mp4;
+pub mod path_resolver;
+
+use path_resolver::{PathKind, PathResolver};
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum Mode {
     Copy,
@@ -21,7 +32,21 @@ impl AsRef<str> for Mode {
         }
     }
 }
-pub fn move_videos<P: AsRef<Path>>(src: P, dst: P, mode: Mode) -> Result<()> {
+/// Copy or move the videos referenced by `src`'s HDR to the paths named by
+/// `dst`'s HDR.
+///
+/// Runs across a rayon thread pool, collecting every per-file failure
+/// instead of silently dropping it; if any file fails, returns a single
+/// aggregated error listing every failed source->dest pair. With
+/// `dry_run`, only prints the planned operations (and any targets that
+/// would be skipped in [`Mode::Move`]) without touching the filesystem.
+pub fn move_videos<P: AsRef<Path>, R: PathResolver>(
+    src: P,
+    dst: P,
+    mode: Mode,
+    resolver: &R,
+    dry_run: bool,
+) -> Result<()> {
     let src_p = src.as_ref();
     let dst_p = dst.as_ref();
 
@@ -36,54 +61,79 @@ pub fn move_videos<P: AsRef<Path>>(src: P, dst: P, mode: Mode) -> Result<()> {
     let l1: Vec<PathBuf> = hdr1
         .data
         .iter()
-        .filter_map(|s| {
-            s.filepath
-                .split('\\')
-                .last()
-                .map(|v| src_p.with_file_name(v))
-        })
+        .map(|s| resolver.resolve(&s.filepath, PathKind::Video, src_p))
         .collect();
     let l2: Vec<PathBuf> = hdr2
         .data
         .iter()
-        .filter_map(|s| {
-            s.filepath
-                .split('\\')
-                .last()
-                .map(|v| dst_p.with_file_name(v))
-        })
+        .map(|s| resolver.resolve(&s.filepath, PathKind::Video, dst_p))
         .collect();
 
+    if dry_run {
+        for (p1, p2) in l1.iter().zip(l2.iter()) {
+            match mode {
+                Mode::Copy => println!("[dry-run] copy {} -> {}", p1.display(), p2.display()),
+                Mode::Move if p2.is_file() => {
+                    println!("[dry-run] skip (target exists) {}", p2.display())
+                }
+                Mode::Move => println!("[dry-run] move {} -> {}", p1.display(), p2.display()),
+            }
+        }
+        return Ok(());
+    }
+
     let bar_template = format!(
         "{} videos: {}",
         mode.as_ref(),
         "{bar:80.cyan/blue} {pos:>7}/{len:7} [{elapsed_precise}]"
     );
-    let style = ProgressStyle::with_template(&bar_template);
-    let tasks = l1.iter().zip(l2.iter()).progress_count(l1.len() as u64);
-
-    let tasks = if let Ok(style) = style {
-        tasks.with_style(style)
-    } else {
-        tasks
-    };
+    let bar = ProgressBar::new(l1.len() as u64);
+    if let Ok(style) = ProgressStyle::with_template(&bar_template) {
+        bar.set_style(style);
+    }
+    let done = AtomicU64::new(0);
+
+    let failures: Vec<(PathBuf, PathBuf, String)> = l1
+        .par_iter()
+        .zip(l2.par_iter())
+        .filter_map(|(p1, p2)| {
+            let result = match mode {
+                Mode::Copy => std::fs::copy(p1, p2).map(|_| ()).map_err(|e| e.to_string()),
+                Mode::Move => {
+                    if p2.is_file() {
+                        Ok(())
+                    } else {
+                        std::fs::rename(p1, p2).map_err(|e| e.to_string())
+                    }
+                }
+            };
+            bar.set_position(done.fetch_add(1, Ordering::SeqCst) + 1);
+            result.err().map(|e| (p1.clone(), p2.clone(), e))
+        })
+        .collect();
 
-    tasks.for_each(|(p1, p2)| match mode {
-        Mode::Copy => {
-            let _ = std::fs::copy(p1, p2).map(|_| ());
-        }
-        Mode::Move => {
-            if !p2.is_file() {
-                let _ = std::fs::rename(p1, p2);
-            }
-        }
-    });
+    bar.finish();
 
     println!(
         "Videos were renamed and {}d in: {}",
         mode.as_ref().to_lowercase(),
         dst_p.parent().unwrap().display(),
     );
+
+    if !failures.is_empty() {
+        let detail = failures
+            .iter()
+            .map(|(src, dst, err)| format!("  {} -> {}: {err}", src.display(), dst.display()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        bail!(
+            "{} of {} video(s) failed to {}:\n{detail}",
+            failures.len(),
+            l1.len(),
+            mode.as_ref().to_lowercase()
+        );
+    }
+
     Ok(())
 }
 
@@ -222,7 +272,11 @@ pub fn modify_raf_file<P: AsRef<Path>>(raf: P, kcd: P) -> Result<()> {
     Ok(())
 }
 
-pub fn modify_video_hdr<P: AsRef<Path>>(hdr: P, prefix: &str) -> Result<PathBuf> {
+pub fn modify_video_hdr<P: AsRef<Path>, R: PathResolver>(
+    hdr: P,
+    prefix: &str,
+    resolver: &R,
+) -> Result<PathBuf> {
     // video folder which contains hdr file and videos
     let hdr = hdr.as_ref();
     let mut input = File::open(hdr).with_context(|| "Fail to open hdr file")?;
@@ -232,7 +286,7 @@ pub fn modify_video_hdr<P: AsRef<Path>>(hdr: P, prefix: &str) -> Result<PathBuf>
     let (_, mut hdr_data) =
         KCDVideoHDR::from_bytes((&buf, 0)).with_context(|| "Fail to parse kcd hdr file")?;
 
-    hdr_data.rename(prefix)?;
+    hdr_data.rename(prefix, resolver)?;
 
     let new_hdr = &hdr.with_file_name(format!("{}.hdr", prefix));
 
@@ -244,7 +298,91 @@ pub fn modify_video_hdr<P: AsRef<Path>>(hdr: P, prefix: &str) -> Result<PathBuf>
     Ok(new_hdr.to_path_buf())
 }
 
-pub fn clone_kcd_with_videos(input: PathBuf, label: String, mode: Mode) -> Result<()> {
+/// Video file extensions recognized when scanning a folder in [`create_hdr`].
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "avi", "mov", "mkv"];
+
+/// Compare two file names "naturally", treating runs of ASCII digits as
+/// numbers so `video2.mp4` sorts before `video10.mp4`.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        return match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(ac), Some(bc)) if ac.is_ascii_digit() && bc.is_ascii_digit() => {
+                let a_num: String =
+                    std::iter::from_fn(|| a_chars.next_if(|c| c.is_ascii_digit())).collect();
+                let b_num: String =
+                    std::iter::from_fn(|| b_chars.next_if(|c| c.is_ascii_digit())).collect();
+                match a_num.parse::<u64>().ok().cmp(&b_num.parse::<u64>().ok()) {
+                    Ordering::Equal => continue,
+                    ord => ord,
+                }
+            }
+            (Some(ac), Some(bc)) => match ac.cmp(bc) {
+                Ordering::Equal => {
+                    a_chars.next();
+                    b_chars.next();
+                    continue;
+                }
+                ord => ord,
+            },
+        };
+    }
+}
+
+/// Scan `folder` for video files, build a [`KCDVideoHDR`] in memory
+/// (videos ordered naturally by filename), and write it as `{label}.hdr`
+/// in the same folder.
+///
+/// The header tag is derived from `label`'s first 4 bytes (zero-padded if
+/// shorter).
+pub fn create_hdr<P: AsRef<Path>>(folder: P, label: &str) -> Result<PathBuf> {
+    let folder = folder.as_ref();
+
+    let mut names: Vec<String> = std::fs::read_dir(folder)
+        .with_context(|| format!("Fail to read folder: {}", folder.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let ext = path.extension()?.to_string_lossy().to_lowercase();
+            VIDEO_EXTENSIONS
+                .contains(&ext.as_str())
+                .then(|| path.file_name().unwrap().to_string_lossy().into_owned())
+        })
+        .collect();
+    names.sort_by(|a, b| natural_cmp(a, b));
+
+    let label_bytes = label.as_bytes();
+    let mut header = [0u8; 4];
+    let n = label_bytes.len().min(4);
+    header[..n].copy_from_slice(&label_bytes[..n]);
+
+    let hdr_data = KCDVideoHDR::builder().header(header).videos(names).build()?;
+
+    let hdr_path = folder.join(format!("{label}.hdr"));
+    let mut output = File::create(&hdr_path)
+        .with_context(|| format!("Fail to create hdr file: {}", hdr_path.display()))?;
+    output.write_all(&hdr_data.to_bytes()?)?;
+    println!("New HDR file was saved as: {}", hdr_path.display());
+    Ok(hdr_path)
+}
+
+/// Clone `input`'s KCD/HDR/videos under `label`. With `dry_run`, only
+/// prints the planned folder/HDR/KCD/video operations without touching the
+/// filesystem (including the folder-create, HDR write, and KCD rename steps
+/// that precede the final [`move_videos`] call).
+pub fn clone_kcd_with_videos<R: PathResolver>(
+    input: PathBuf,
+    label: String,
+    mode: Mode,
+    resolver: &R,
+    dry_run: bool,
+) -> Result<()> {
     let kcd = input;
     if !kcd.is_file() {
         bail!("KCD was not a file. Abort the process")
@@ -258,20 +396,170 @@ pub fn clone_kcd_with_videos(input: PathBuf, label: String, mode: Mode) -> Resul
     }
     let cwd = kcd.parent().unwrap();
     let new_video_folder = cwd.join(&label);
+    let new_hdr = new_video_folder.join(format!("{label}.hdr"));
+    let new_kcd = kcd.with_file_name(format!("{label}.kcd"));
+
+    if dry_run {
+        println!("[dry-run] create folder {}", new_video_folder.display());
+        println!(
+            "[dry-run] write HDR {} (relabeled from {})",
+            new_hdr.display(),
+            hdr.display()
+        );
+        println!(
+            "[dry-run] rename KCD {} -> {}",
+            kcd.display(),
+            new_kcd.display()
+        );
+        for (name, _) in video_paths(&hdr, resolver)? {
+            let filename = resolver
+                .split(&name)
+                .last()
+                .cloned()
+                .unwrap_or_else(|| name.clone());
+            let dst = new_video_folder.join(&filename);
+            match mode {
+                Mode::Copy => println!("[dry-run] copy {filename} -> {}", dst.display()),
+                Mode::Move if dst.is_file() => {
+                    println!("[dry-run] skip (target exists) {}", dst.display())
+                }
+                Mode::Move => println!("[dry-run] move {filename} -> {}", dst.display()),
+            }
+        }
+        return Ok(());
+    }
+
     let _ = std::fs::create_dir(&new_video_folder);
-    let from_hdr = modify_video_hdr(&hdr, &label)?;
+    let from_hdr = modify_video_hdr(&hdr, &label, resolver)?;
     let to_hdr = new_video_folder.join(from_hdr.file_name().unwrap());
     std::fs::rename(
         &from_hdr,
         &to_hdr,
     )?;
     let new_kcd_name = modify_kcrmovie_text(&kcd, &from_hdr, Mode::Copy)?;
-    std::fs::rename(&new_kcd_name, new_kcd_name.with_file_name(format!("{}.kcd", &label)))?;
-    move_videos(&hdr, &to_hdr, mode)
+    std::fs::rename(&new_kcd_name, &new_kcd)?;
+    move_videos(&hdr, &to_hdr, mode, resolver, false)
+}
+
+/// Bundle a KCD file, its HDR, and every video the HDR references into a
+/// single [`archive`] file, so the dataset can be moved around as one file.
+pub fn pack_dataset<P: AsRef<Path>, R: PathResolver>(
+    kcd: P,
+    archive_path: P,
+    resolver: &R,
+) -> Result<()> {
+    let kcd = kcd.as_ref();
+    if !kcd.is_file() {
+        bail!("KCD was not a file. Abort the pack process")
+    }
+    let tag = kcd.file_stem().map(|x| x.to_string_lossy()).unwrap();
+    let hdr = kcd.with_file_name(tag.as_ref()).join(format!("{}.hdr", &tag));
+    if !hdr.is_file() {
+        bail!("HDR was not existed. Abort the pack process")
+    }
+
+    let mut members = vec![
+        archive::Member {
+            name: kcd.file_name().unwrap().to_string_lossy().into_owned(),
+            path: kcd.to_path_buf(),
+            kind: archive::MemberKind::Kcd,
+        },
+        archive::Member {
+            name: hdr.file_name().unwrap().to_string_lossy().into_owned(),
+            path: hdr.clone(),
+            kind: archive::MemberKind::Hdr,
+        },
+    ];
+    for (name, video_path) in video_paths(&hdr, resolver)? {
+        let name = resolver
+            .split(&name)
+            .last()
+            .cloned()
+            .unwrap_or_else(|| name.clone());
+        members.push(archive::Member {
+            name,
+            path: video_path,
+            kind: archive::MemberKind::Video,
+        });
+    }
+
+    archive::pack(archive_path, &members)
+}
+
+/// Extract every member of a [`pack_dataset`] archive into `dest_dir`.
+pub fn unpack_dataset<P: AsRef<Path>>(archive_path: P, dest_dir: P) -> Result<()> {
+    archive::unpack_all(archive_path, dest_dir)
+}
+
+/// Parse `hdr` and resolve each entry's stored filepath to its on-disk
+/// video file via `resolver`.
+///
+/// Returns `(stored filepath, resolved path)` pairs.
+pub(crate) fn video_paths<P: AsRef<Path>, R: PathResolver>(
+    hdr: P,
+    resolver: &R,
+) -> Result<Vec<(String, PathBuf)>> {
+    let hdr = hdr.as_ref();
+    let mut buf: Vec<u8> = Vec::new();
+    File::open(hdr)
+        .with_context(|| format!("Fail to open hdr file: {}", hdr.display()))?
+        .read_to_end(&mut buf)?;
+    let (_, hdr_data) =
+        KCDVideoHDR::from_bytes((&buf, 0)).with_context(|| "Fail to parse kcd hdr file")?;
+
+    Ok(hdr_data
+        .data
+        .iter()
+        .map(|block| {
+            let resolved = resolver.resolve(&block.filepath, PathKind::Video, hdr);
+            (block.filepath.clone(), resolved)
+        })
+        .collect())
+}
+
+/// Outcome of checking a single [`VideoBlock`]'s referenced file against its
+/// MP4 box structure.
+#[derive(Debug)]
+pub struct VideoVerification {
+    pub filepath: String,
+    pub status: Result<mp4::Mp4Info, String>,
+}
+
+/// For each video referenced by `hdr`, confirm the sibling file exists and
+/// is a well-formed MP4, reporting duration/resolution/codec.
+///
+/// Prints a summary table and returns one [`VideoVerification`] per entry so
+/// a broken link (missing file or box sizes that overrun their parent) can
+/// be caught before a `Clone`/`Move` operation.
+pub fn verify_hdr<P: AsRef<Path>, R: PathResolver>(hdr: P, resolver: &R) -> Result<Vec<VideoVerification>> {
+    let results: Vec<VideoVerification> = video_paths(hdr, resolver)?
+        .into_iter()
+        .map(|(filepath, video_path)| VideoVerification {
+            filepath,
+            status: mp4::parse(&video_path).map_err(|e| e.to_string()),
+        })
+        .collect();
+
+    println!("{:<48} {:>6} {:>10} {:>12}  codec", "file", "ok", "duration", "resolution");
+    for r in &results {
+        match &r.status {
+            Ok(info) => println!(
+                "{:<48} {:>6} {:>9.2}s {:>12}  {}",
+                r.filepath,
+                "ok",
+                info.duration_secs(),
+                format!("{}x{}", info.width, info.height),
+                info.codec,
+            ),
+            Err(e) => println!("{:<48} {:>6} {}", r.filepath, "FAIL", e),
+        }
+    }
+
+    Ok(results)
 }
 
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
-struct KCDVideoHDR {
+pub struct KCDVideoHDR {
     #[deku(bytes_read = "4")]
     header: Vec<u8>,
     #[deku(bytes = "4")]
@@ -300,23 +588,89 @@ impl KCDVideoHDR {
         }
         Ok(())
     }
-    fn rename(&mut self, prefix: &str) -> Result<()> {
+    /// Swap the stored prefix of every video's path for `prefix`, via
+    /// `resolver`'s [`split`](PathResolver::split)/[`join`](PathResolver::join)
+    /// on the raw `\`-delimited string. This only rewrites the prefix
+    /// component; it does not consult [`resolve`](PathResolver::resolve), so
+    /// a custom resolver's on-disk remapping has no effect on the bytes
+    /// written back out here.
+    fn rename<R: PathResolver>(&mut self, prefix: &str, resolver: &R) -> Result<()> {
         if prefix.len() > 120 {
             bail!("Prefix is too long (<= 120 charaters")
         }
         self.data.iter_mut().for_each(|block| {
-            let filepath_s: Vec<&str> = block.filepath.split('\\').collect();
-            if let Some(&old_prefix) = filepath_s.first() {
-                let new_filepath = block.filepath.replace(old_prefix, prefix);
-                block.filepath = new_filepath;
+            let mut components = resolver.split(&block.filepath);
+            if let Some(old_prefix) = components.first_mut() {
+                *old_prefix = prefix.to_string();
             }
+            block.filepath = resolver.join(&components);
         });
         Ok(())
     }
+
+    /// Start building a fresh [`KCDVideoHDR`] from scratch, e.g. when one
+    /// was lost or a video folder was never paired with one.
+    pub fn builder() -> KCDVideoHDRBuilder {
+        KCDVideoHDRBuilder::default()
+    }
+}
+
+/// Builder for a [`KCDVideoHDR`]: accepts the 4-byte header tag and an
+/// ordered list of video file names, and emits correctly-ordered 292-byte
+/// `VideoBlock`s with `count` set automatically.
+#[derive(Debug, Default)]
+pub struct KCDVideoHDRBuilder {
+    header: Vec<u8>,
+    videos: Vec<String>,
+}
+
+impl KCDVideoHDRBuilder {
+    /// Set the 4-byte header tag.
+    pub fn header(mut self, header: [u8; 4]) -> Self {
+        self.header = header.to_vec();
+        self
+    }
+
+    /// Append one video file name, in the order its block should appear.
+    pub fn video(mut self, name: impl Into<String>) -> Self {
+        self.videos.push(name.into());
+        self
+    }
+
+    /// Append several video file names, in order.
+    pub fn videos(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.videos.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Build the [`KCDVideoHDR`], setting `count` from the number of videos
+    /// added.
+    pub fn build(self) -> Result<KCDVideoHDR> {
+        if self.header.len() != 4 {
+            bail!("HDR header tag must be exactly 4 bytes");
+        }
+        if let Some(long) = self.videos.iter().find(|name| name.len() > 256) {
+            bail!("Video file name is too long (> 256 bytes): {long}");
+        }
+        let data: Vec<VideoBlock> = self
+            .videos
+            .into_iter()
+            .map(|filepath| VideoBlock {
+                _head: vec![0u8; 16],
+                filepath,
+                _padding: vec![0u8; 20],
+            })
+            .collect();
+        Ok(KCDVideoHDR {
+            header: self.header,
+            count: data.len() as u32,
+            data,
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
-struct VideoBlock {
+pub struct VideoBlock {
     #[deku(bytes_read = "16")]
     _head: Vec<u8>,
     #[deku(
@@ -389,6 +743,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn builder_round_trip() -> anyhow::Result<()> {
+        let kcd = KCDVideoHDR::builder()
+            .header(*b"TEST")
+            .videos(["a.mp4", "b.mp4", "c.mp4"])
+            .build()?;
+        let kcd_bytes = kcd.to_bytes()?;
+        let (_, reparsed) = KCDVideoHDR::from_bytes((&kcd_bytes, 0))?;
+        assert_eq!(kcd, reparsed);
+        Ok(())
+    }
+
+    #[test]
+    fn move_videos_dry_run_touches_no_files() -> anyhow::Result<()> {
+        let src_hdr = KCDVideoHDR::builder()
+            .header(*b"SRC\0")
+            .videos(["a.mp4", "b.mp4"])
+            .build()?;
+        let dst_hdr = KCDVideoHDR::builder()
+            .header(*b"DST\0")
+            .videos(["a_out.mp4", "b_out.mp4"])
+            .build()?;
+
+        let src_path = "./move_videos_test_src.hdr";
+        let dst_path = "./move_videos_test_dst.hdr";
+        File::create(src_path)?.write_all(&src_hdr.to_bytes()?)?;
+        File::create(dst_path)?.write_all(&dst_hdr.to_bytes()?)?;
+
+        move_videos(src_path, dst_path, Mode::Copy, &path_resolver::DefaultPathResolver, true)?;
+
+        assert!(!Path::new("./a_out.mp4").is_file());
+        assert!(!Path::new("./b_out.mp4").is_file());
+        Ok(())
+    }
+
     #[test]
     fn test_bar() -> anyhow::Result<()> {
         let mode = Mode::Move;
@@ -2,7 +2,8 @@ use anyhow::Result;
 use clap::error::ErrorKind;
 use clap::{CommandFactory, Parser, ValueHint};
 use kcd_utils::{
-    clone_kcd_with_videos, modify_kcrmovie_text, modify_raf_file, modify_video_hdr, move_videos,
+    clone_kcd_with_videos, create_hdr, modify_kcrmovie_text, modify_raf_file, modify_video_hdr,
+    move_videos, pack_dataset, path_resolver::DefaultPathResolver, unpack_dataset, verify_hdr,
     Mode,
 };
 use std::path::PathBuf;
@@ -76,6 +77,10 @@ enum Utils {
         /// Method to move the video  (Default: Copy)
         #[arg(short, long, value_enum, value_name ="MODE", default_value_t  = Mode::Copy)]
         mode: Mode,
+
+        /// Preview the planned operations without touching the filesystem.
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Clone existing KCD and videos into new labeled KCD file
     #[clap(arg_required_else_help = true)]
@@ -91,6 +96,70 @@ enum Utils {
         /// Method to generate the KCD and videos file  (Default: Copy)
         #[arg(short, long, value_enum, value_name ="MODE", default_value_t  = Mode::Copy)]
         mode: Mode,
+
+        /// Preview the planned operations without touching the filesystem.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Check the videos referenced by an HDR file are present and non-truncated.
+    #[clap(arg_required_else_help = true)]
+    Verify {
+        /// Specify the input HDR file.
+        #[arg(short, long, value_name = "HDR FILE", value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+    },
+
+    /// Find near-duplicate videos across one or more HDR files.
+    #[clap(arg_required_else_help = true)]
+    Dedup {
+        /// Specify one or more HDR files to compare videos across.
+        #[arg(short, long, value_name = "HDR FILE", value_hint = ValueHint::FilePath, num_args = 1..)]
+        input: Vec<PathBuf>,
+
+        /// Maximum Hamming distance between hashes to count as a duplicate.
+        #[arg(short, long, default_value_t = 8)]
+        tolerance: u32,
+    },
+
+    /// Bundle a KCD, its HDR, and all referenced videos into one archive file.
+    #[clap(arg_required_else_help = true)]
+    Pack {
+        /// Specify the input KCD file.
+        #[arg(short, long, value_name = "KCD FILE", value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Specify the output archive file.
+        #[arg(short, long, value_name = "ARCHIVE FILE", value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+    },
+
+    /// Extract a dataset archive produced by `pack`.
+    #[clap(arg_required_else_help = true)]
+    Unpack {
+        /// Specify the input archive file.
+        #[arg(short, long, value_name = "ARCHIVE FILE", value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Extract only this member (e.g. `foo.mp4`) instead of everything.
+        #[arg(short, long)]
+        member: Option<String>,
+
+        /// Specify the output directory (or, with `--member`, the output file).
+        #[arg(short, long, value_name = "PATH", value_hint = ValueHint::AnyPath)]
+        output: PathBuf,
+    },
+
+    /// Scan a folder of videos and build a fresh HDR file for them.
+    #[clap(arg_required_else_help = true)]
+    Create {
+        /// Specify the folder containing the video files (the new HDR is written alongside them).
+        #[arg(short, long, value_name = "VIDEO FOLDER", value_hint = ValueHint::DirPath)]
+        folder: PathBuf,
+
+        /// Specify the text for labeling the new HDR file.
+        #[arg(short, long)]
+        label: String,
     },
 }
 
@@ -107,9 +176,43 @@ fn main() -> Result<()> {
         Utils::Hdr {
             input,
             label: prefix,
-        } => modify_video_hdr(input, &prefix).map(|_| ()),
-        Utils::Video { src, dst, mode } => move_videos(src, dst, mode),
-        Utils::Clone { input, label, mode } => clone_kcd_with_videos(input, label, mode),
+        } => modify_video_hdr(input, &prefix, &DefaultPathResolver).map(|_| ()),
+        Utils::Video {
+            src,
+            dst,
+            mode,
+            dry_run,
+        } => move_videos(src, dst, mode, &DefaultPathResolver, dry_run),
+        Utils::Clone {
+            input,
+            label,
+            mode,
+            dry_run,
+        } => clone_kcd_with_videos(input, label, mode, &DefaultPathResolver, dry_run),
+        Utils::Verify { input } => verify_hdr(input, &DefaultPathResolver).map(|_| ()),
+        Utils::Dedup { input, tolerance } => {
+            kcd_utils::dedup::find_duplicates(&input, tolerance, &DefaultPathResolver).map(|clusters| {
+                if clusters.is_empty() {
+                    println!("No near-duplicate videos found.");
+                }
+                for (i, cluster) in clusters.iter().enumerate() {
+                    println!("Cluster {}:", i + 1);
+                    for (hdr, name) in &cluster.members {
+                        println!("  {} :: {}", hdr.display(), name);
+                    }
+                }
+            })
+        }
+        Utils::Pack { input, output } => pack_dataset(input, output, &DefaultPathResolver),
+        Utils::Unpack {
+            input,
+            member,
+            output,
+        } => match member {
+            Some(member) => kcd_utils::archive::unpack_member(input, &member, output),
+            None => unpack_dataset(input, output),
+        },
+        Utils::Create { folder, label } => create_hdr(folder, &label).map(|_| ()),
     };
 
     match res {
@@ -0,0 +1,298 @@
+//! Minimal MP4 "box" (atom) reader.
+//!
+//! This only understands enough of the ISO BMFF box layout to sanity-check
+//! the video files referenced by an HDR: whether the file exists, whether
+//! a top-level box or a box walked inside `moov` declares a size that
+//! overruns its parent (a truncation signal), and
+//! the duration/resolution/codec reported by `moov/mvhd` and the first
+//! `avc1` sample entry. It is not a general-purpose MP4 parser.
+
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Duration/resolution/codec summary extracted from a single MP4 file.
+#[derive(Debug, Clone, Default)]
+pub struct Mp4Info {
+    pub timescale: u32,
+    pub duration: u64,
+    pub width: u16,
+    pub height: u16,
+    pub codec: String,
+}
+
+impl Mp4Info {
+    /// Duration in seconds, derived from `duration / timescale`.
+    pub fn duration_secs(&self) -> f64 {
+        if self.timescale == 0 {
+            0.0
+        } else {
+            self.duration as f64 / self.timescale as f64
+        }
+    }
+}
+
+struct BoxHeader {
+    kind: [u8; 4],
+    payload_start: u64,
+    payload_end: u64,
+}
+
+fn read_box_header<R: Read + Seek>(reader: &mut R, end: u64) -> Result<Option<BoxHeader>> {
+    let start = reader.stream_position()?;
+    if start + 8 > end {
+        return Ok(None);
+    }
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    let mut size = u32::from_be_bytes(buf[0..4].try_into().unwrap()) as u64;
+    let kind: [u8; 4] = buf[4..8].try_into().unwrap();
+
+    let header_len: u64 = if size == 1 {
+        let mut largesize = [0u8; 8];
+        reader.read_exact(&mut largesize)?;
+        size = u64::from_be_bytes(largesize);
+        16
+    } else {
+        8
+    };
+
+    let payload_start = start + header_len;
+    let payload_end = if size == 0 { end } else { start + size };
+
+    if payload_end > end || payload_end < payload_start {
+        bail!(
+            "box `{}` at offset {start} claims size {size}, which overruns its parent (ends at {end})",
+            String::from_utf8_lossy(&kind)
+        );
+    }
+
+    Ok(Some(BoxHeader {
+        kind,
+        payload_start,
+        payload_end,
+    }))
+}
+
+/// Walk the boxes covering `[start, end)` and return the payload range of
+/// the first direct child whose fourcc is `kind`.
+fn find_child<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    kind: &[u8; 4],
+) -> Result<Option<(u64, u64)>> {
+    reader.seek(SeekFrom::Start(start))?;
+    while let Some(b) = read_box_header(reader, end)? {
+        if &b.kind == kind {
+            return Ok(Some((b.payload_start, b.payload_end)));
+        }
+        reader.seek(SeekFrom::Start(b.payload_end))?;
+    }
+    Ok(None)
+}
+
+/// Walk the boxes covering `[start, end)` and return the payload ranges of
+/// every direct child whose fourcc is `kind` (unlike [`find_child`], which
+/// stops at the first match).
+fn find_children<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    kind: &[u8; 4],
+) -> Result<Vec<(u64, u64)>> {
+    reader.seek(SeekFrom::Start(start))?;
+    let mut out = Vec::new();
+    while let Some(b) = read_box_header(reader, end)? {
+        if &b.kind == kind {
+            out.push((b.payload_start, b.payload_end));
+        }
+        reader.seek(SeekFrom::Start(b.payload_end))?;
+    }
+    Ok(out)
+}
+
+/// Recursively resolve a path of box fourccs, e.g. `[moov, trak, mdia]`.
+fn find_path<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    path: &[&[u8; 4]],
+) -> Result<Option<(u64, u64)>> {
+    let mut range = (start, end);
+    for kind in path {
+        let Some(next) = find_child(reader, range.0, range.1, kind)? else {
+            return Ok(None);
+        };
+        range = next;
+    }
+    Ok(Some(range))
+}
+
+fn read_mvhd<R: Read + Seek>(reader: &mut R, start: u64) -> Result<(u32, u64)> {
+    reader.seek(SeekFrom::Start(start))?;
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version)?;
+
+    if version[0] == 1 {
+        reader.seek(SeekFrom::Current(16))?; // creation_time + modification_time (u64 each)
+        let mut timescale = [0u8; 4];
+        reader.read_exact(&mut timescale)?;
+        let mut duration = [0u8; 8];
+        reader.read_exact(&mut duration)?;
+        Ok((u32::from_be_bytes(timescale), u64::from_be_bytes(duration)))
+    } else {
+        reader.seek(SeekFrom::Current(8))?; // creation_time + modification_time (u32 each)
+        let mut timescale = [0u8; 4];
+        reader.read_exact(&mut timescale)?;
+        let mut duration = [0u8; 4];
+        reader.read_exact(&mut duration)?;
+        Ok((
+            u32::from_be_bytes(timescale),
+            u32::from_be_bytes(duration) as u64,
+        ))
+    }
+}
+
+fn read_avc1_resolution<R: Read + Seek>(reader: &mut R, payload_start: u64) -> Result<(u16, u16)> {
+    // VisualSampleEntry: 6 reserved + 2 data_reference_index + 2 pre_defined
+    // + 2 reserved + 12 pre_defined = 24 bytes, then width/height (u16 each).
+    reader.seek(SeekFrom::Start(payload_start + 24))?;
+    let mut wh = [0u8; 4];
+    reader.read_exact(&mut wh)?;
+    Ok((
+        u16::from_be_bytes(wh[0..2].try_into().unwrap()),
+        u16::from_be_bytes(wh[2..4].try_into().unwrap()),
+    ))
+}
+
+/// Parse just enough of `path` to report its duration and the
+/// resolution/codec of its video track.
+///
+/// Fails if the file is missing, if `moov/mvhd` cannot be found, or if a
+/// top-level box, or a box walked inside `moov`, declares a size that
+/// overruns its parent (a truncated-file signal). Each
+/// `trak` under `moov` is tried in turn, so a non-video track muxed before
+/// the video track (e.g. audio-first) doesn't stop the video track from
+/// being found.
+pub fn parse(path: impl AsRef<Path>) -> Result<Mp4Info> {
+    let path = path.as_ref();
+    let mut file =
+        File::open(path).with_context(|| format!("video file is missing: {}", path.display()))?;
+    let len = file.metadata()?.len();
+
+    // Walk every top-level box, not just those up to `moov`, so a truncated
+    // box after `moov` (e.g. `mdat`) is caught too.
+    let (moov_start, moov_end) = find_children(&mut file, 0, len, b"moov")?
+        .first()
+        .copied()
+        .with_context(|| format!("no `moov` box found in {}", path.display()))?;
+
+    let (mvhd_start, _) = find_child(&mut file, moov_start, moov_end, b"mvhd")?
+        .with_context(|| format!("no `moov/mvhd` box found in {}", path.display()))?;
+    let (timescale, duration) = read_mvhd(&mut file, mvhd_start)?;
+
+    let mut info = Mp4Info {
+        timescale,
+        duration,
+        width: 0,
+        height: 0,
+        codec: String::new(),
+    };
+
+    for (trak_start, trak_end) in find_children(&mut file, moov_start, moov_end, b"trak")? {
+        let Some((stsd_start, stsd_end)) = find_path(
+            &mut file,
+            trak_start,
+            trak_end,
+            &[b"mdia", b"minf", b"stbl", b"stsd"],
+        )?
+        else {
+            continue;
+        };
+        // stsd body is version/flags (4 bytes) + entry_count (4 bytes) before
+        // the first sample entry box.
+        if let Some((avc1_start, _)) = find_child(&mut file, stsd_start + 8, stsd_end, b"avc1")? {
+            let (width, height) = read_avc1_resolution(&mut file, avc1_start)?;
+            info.width = width;
+            info.height = height;
+            info.codec = "avc1".to_string();
+            break;
+        }
+    }
+
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn make_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&((8 + payload.len()) as u32).to_be_bytes());
+        out.extend_from_slice(kind);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    #[test]
+    fn read_box_header_bails_when_child_overruns_parent() {
+        // Claims size 100 but the parent range given as `end` is only 8
+        // bytes long.
+        let mut buf = vec![0u8; 8];
+        buf[0..4].copy_from_slice(&100u32.to_be_bytes());
+        buf[4..8].copy_from_slice(b"trak");
+        let mut reader = Cursor::new(buf);
+
+        let err = read_box_header(&mut reader, 8).unwrap_err();
+        assert!(err.to_string().contains("overruns its parent"));
+    }
+
+    #[test]
+    fn parse_finds_avc1_in_a_later_trak_when_the_first_trak_has_no_video() -> Result<()> {
+        let mut avc1_payload = vec![0u8; 24];
+        avc1_payload.extend_from_slice(&320u16.to_be_bytes());
+        avc1_payload.extend_from_slice(&240u16.to_be_bytes());
+        let avc1 = make_box(b"avc1", &avc1_payload);
+
+        let mut stsd_payload = vec![0u8; 8]; // version/flags + entry_count
+        stsd_payload.extend_from_slice(&avc1);
+        let stsd = make_box(b"stsd", &stsd_payload);
+        let stbl = make_box(b"stbl", &stsd);
+        let minf = make_box(b"minf", &stbl);
+        let mdia = make_box(b"mdia", &minf);
+        let video_trak = make_box(b"trak", &mdia);
+
+        // The first `trak` has no `mdia` at all (e.g. an audio track this
+        // parser doesn't understand) -- `parse` must not stop here.
+        let empty_trak = make_box(b"trak", &[]);
+
+        let mut mvhd_payload = vec![0u8; 4]; // version/flags (version 0)
+        mvhd_payload.extend_from_slice(&[0u8; 8]); // creation + modification
+        mvhd_payload.extend_from_slice(&1000u32.to_be_bytes()); // timescale
+        mvhd_payload.extend_from_slice(&2000u32.to_be_bytes()); // duration
+        let mvhd = make_box(b"mvhd", &mvhd_payload);
+
+        let mut moov_payload = Vec::new();
+        moov_payload.extend_from_slice(&mvhd);
+        moov_payload.extend_from_slice(&empty_trak);
+        moov_payload.extend_from_slice(&video_trak);
+        let moov = make_box(b"moov", &moov_payload);
+
+        let path = "./mp4_test_multi_trak.mp4";
+        std::fs::write(path, &moov)?;
+
+        let info = parse(path)?;
+        assert_eq!(info.codec, "avc1");
+        assert_eq!(info.width, 320);
+        assert_eq!(info.height, 240);
+        assert_eq!(info.timescale, 1000);
+        assert_eq!(info.duration, 2000);
+        Ok(())
+    }
+}
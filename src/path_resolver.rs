@@ -0,0 +1,114 @@
+//! Pluggable resolution from the `\`-delimited paths stored in KCD/HDR
+//! files to native [`PathBuf`]s.
+//!
+//! KCD/HDR files are authored on Windows and always store `\`-delimited
+//! paths, regardless of what platform later reads them. A [`PathResolver`]
+//! centralizes that normalization behind one trait, instead of every call
+//! site assuming `\` is the host's separator.
+//!
+//! This only covers *lookup*: [`KCDVideoHDR::rename`](crate::KCDVideoHDR::rename),
+//! which rewrites the stored prefix of every path when an HDR is relabeled,
+//! works on the raw `\`-delimited strings via [`split`](PathResolver::split)
+//! and [`join`](PathResolver::join) and does not consult [`resolve`]. A
+//! custom resolver changes where a stored path is found on disk; it does
+//! not change the bytes a relabeled HDR writes back out.
+
+use std::path::{Path, PathBuf};
+
+/// Which kind of stored path is being resolved, so a custom resolver can
+/// special-case e.g. only video paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathKind {
+    Kcd,
+    Hdr,
+    Video,
+}
+
+/// Normalizes the `\`-delimited paths stored on disk into native
+/// [`PathBuf`]s.
+///
+/// Default method bodies implement the behavior this crate always used
+/// (split/join on literal `\`, resolve relative to a sibling `base`); a
+/// custom resolver typically only needs to override [`resolve`] to map a
+/// stored drive-letter prefix onto a real mount point.
+///
+/// [`resolve`]: PathResolver::resolve
+pub trait PathResolver {
+    /// Split a stored path into its `\`-delimited components.
+    fn split(&self, stored: &str) -> Vec<String> {
+        stored.split('\\').map(str::to_string).collect()
+    }
+
+    /// Join components back into the on-disk `\`-delimited form.
+    fn join(&self, components: &[String]) -> String {
+        components.join("\\")
+    }
+
+    /// Resolve a stored path of `kind` to a native path, relative to
+    /// `base` (the directory the owning KCD/HDR/video file lives in).
+    fn resolve(&self, stored: &str, _kind: PathKind, base: &Path) -> PathBuf {
+        let name = self
+            .split(stored)
+            .last()
+            .cloned()
+            .unwrap_or_else(|| stored.to_string());
+        base.with_file_name(name)
+    }
+}
+
+/// The resolver this crate always used: splits/joins on literal `\` and
+/// resolves a stored path's final component next to `base`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultPathResolver;
+
+impl PathResolver for DefaultPathResolver {}
+
+/// A [`PathResolver`] built from closures, for callers that want to inject
+/// custom mapping (e.g. relocating a drive-letter prefix to a mount point)
+/// without writing a whole new type.
+pub struct LoaderPathResolver<F> {
+    loader: F,
+}
+
+impl<F> LoaderPathResolver<F>
+where
+    F: Fn(&str, PathKind, &Path) -> PathBuf,
+{
+    pub fn new(loader: F) -> Self {
+        Self { loader }
+    }
+}
+
+impl<F> PathResolver for LoaderPathResolver<F>
+where
+    F: Fn(&str, PathKind, &Path) -> PathBuf,
+{
+    fn resolve(&self, stored: &str, kind: PathKind, base: &Path) -> PathBuf {
+        (self.loader)(stored, kind, base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loader_path_resolver_remaps_a_drive_letter_prefix_to_a_mount_point() {
+        let resolver = LoaderPathResolver::new(|stored: &str, kind, _base: &Path| {
+            assert_eq!(kind, PathKind::Video);
+            let rest = stored.strip_prefix(r"D:\").unwrap_or(stored);
+            PathBuf::from("/mnt/d").join(rest.replace('\\', "/"))
+        });
+
+        let resolved = resolver.resolve(
+            r"D:\EEG_test\abc.0001\video1.mp4",
+            PathKind::Video,
+            Path::new(r"D:\EEG_test\abc.0001\abc.0001.hdr"),
+        );
+
+        assert_eq!(
+            resolved,
+            PathBuf::from("/mnt/d/EEG_test/abc.0001/video1.mp4")
+        );
+    }
+}